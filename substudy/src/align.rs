@@ -0,0 +1,66 @@
+//! Aligning a foreign-language subtitle file against an optional
+//! native-language one, by overlapping time period.
+
+use crate::{
+    srt::{Cue, SubtitleFile},
+    time::Period,
+};
+
+/// One aligned span of dialog: a time period taken from a foreign cue,
+/// its lines, and the lines of any native cues it overlaps.
+#[derive(Debug, Clone)]
+pub struct AlignedCue {
+    /// The period during which this dialog is displayed, taken from the
+    /// foreign-language cue.
+    pub period: Period,
+    /// The foreign-language lines displayed during this period.
+    pub foreign: Vec<String>,
+    /// The native-language lines of any cues overlapping this period.
+    pub native: Vec<String>,
+}
+
+fn overlaps(a: &Cue, b: &Cue) -> bool {
+    a.period.begin() < b.period.end() && b.period.begin() < a.period.end()
+}
+
+/// Align `foreign` against `native`: one [`AlignedCue`] per foreign cue,
+/// carrying the text of every native cue it overlaps.
+pub fn align_cues(foreign: &SubtitleFile, native: Option<&SubtitleFile>) -> Vec<AlignedCue> {
+    foreign
+        .cues
+        .iter()
+        .map(|foreign_cue| {
+            let native_lines = native
+                .map(|native| {
+                    native
+                        .cues
+                        .iter()
+                        .filter(|native_cue| overlaps(foreign_cue, native_cue))
+                        .flat_map(|native_cue| native_cue.lines.iter().cloned())
+                        .collect()
+                })
+                .unwrap_or_default();
+            AlignedCue {
+                period: foreign_cue.period,
+                foreign: foreign_cue.lines.clone(),
+                native: native_lines,
+            }
+        })
+        .collect()
+}
+
+/// Combine two subtitle files into a single bilingual file: each cue
+/// keeps the foreign cue's timing, with its own lines followed by the
+/// lines of any native cues it overlaps.
+pub fn combine_files(foreign: &SubtitleFile, native: &SubtitleFile) -> SubtitleFile {
+    let cues = align_cues(foreign, Some(native))
+        .into_iter()
+        .enumerate()
+        .map(|(i, aligned)| {
+            let mut lines = aligned.foreign;
+            lines.extend(aligned.native);
+            Cue::new(i + 1, aligned.period, lines)
+        })
+        .collect();
+    SubtitleFile::new(cues)
+}
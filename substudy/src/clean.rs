@@ -0,0 +1,50 @@
+//! Heuristics for removing non-dialog cues from a subtitle file.
+
+use crate::srt::Cue;
+
+/// Does this line look like a sound effect or hearing-impaired annotation
+/// (e.g. `[music playing]`, `(door slams)`) rather than spoken dialog?
+fn looks_like_sound_effect(line: &str) -> bool {
+    let trimmed = line.trim();
+    (trimmed.starts_with('[') && trimmed.ends_with(']'))
+        || (trimmed.starts_with('(') && trimmed.ends_with(')'))
+        || trimmed.starts_with('♪')
+}
+
+/// Strip ASS/SSA override blocks (`{\i1}`, `{\pos(10,20)}`, ...) and
+/// drawing-mode tags from a line of cue text, leaving only the dialog.
+fn strip_ass_tags(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut depth = 0u32;
+    for c in line.chars() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            _ if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Remove cues that don't look like dialog: empty cues, and cues made up
+/// entirely of sound-effect annotations. Also strips any ASS/SSA styling
+/// or drawing tags left over from parsing, since those were never part
+/// of the spoken dialog.
+pub fn clean_cues(cues: Vec<Cue>) -> Vec<Cue> {
+    cues.into_iter()
+        .map(|mut cue| {
+            cue.lines = cue
+                .lines
+                .into_iter()
+                .map(|l| strip_ass_tags(&l).trim().to_owned())
+                .collect();
+            cue
+        })
+        .filter(|cue| {
+            !cue.lines.is_empty()
+                && cue.lines.iter().any(|l| !l.is_empty())
+                && !cue.lines.iter().all(|l| looks_like_sound_effect(l))
+        })
+        .collect()
+}
@@ -0,0 +1,39 @@
+//! Decoding the audio track of a video file into raw PCM samples, via
+//! `ffmpeg`.
+
+use std::{path::Path, process::Command};
+
+use crate::Result;
+
+/// Decode the audio track of `path` to mono, 16-bit PCM at `sample_rate`
+/// Hz, returning the samples normalized to `[-1.0, 1.0]`.
+pub fn decode_mono_samples(path: &Path, sample_rate: u32) -> Result<Vec<f32>> {
+    let sample_rate = sample_rate.to_string();
+    let output = Command::new("ffmpeg")
+        .args(["-v", "quiet", "-i"])
+        .arg(path)
+        .args([
+            "-vn",
+            "-ac",
+            "1",
+            "-ar",
+            sample_rate.as_str(),
+            "-f",
+            "s16le",
+            "-",
+        ])
+        .output()
+        .map_err(|e| anyhow::anyhow!("could not run ffmpeg on {}: {}", path.display(), e))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "ffmpeg failed to decode audio from {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(output
+        .stdout
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0)
+        .collect())
+}
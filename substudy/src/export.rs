@@ -0,0 +1,435 @@
+//! Exporting a video plus its subtitles into a form suitable for
+//! language study: Anki flashcards, an HTML review page, standalone
+//! audio tracks, or (see [`export_video`]) a hardsubbed bilingual video.
+
+use std::{fs, path::PathBuf, process::Command};
+
+use crate::{
+    align::{self, AlignedCue},
+    srt::SubtitleFile,
+    time::Period,
+    ui::Ui,
+    video::Video,
+    Result,
+};
+
+/// Everything needed to export a video and its subtitles: the source
+/// video, the subtitles to export, and the directory the export is
+/// written to.
+pub struct Exporter {
+    video: Video,
+    foreign_subs: SubtitleFile,
+    native_subs: Option<SubtitleFile>,
+    out_dir: PathBuf,
+}
+
+impl Exporter {
+    /// Set up a new export of `kind` (`"csv"`, `"review"`, `"tracks"`,
+    /// `"video"`, ...), creating its output directory.
+    pub fn new(
+        video: Video,
+        foreign_subs: SubtitleFile,
+        native_subs: Option<SubtitleFile>,
+        kind: &str,
+    ) -> Result<Exporter> {
+        let stem = video
+            .path()
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("export");
+        let out_dir = PathBuf::from(format!("{}_{}", stem, kind));
+        fs::create_dir_all(&out_dir)?;
+        Ok(Exporter {
+            video,
+            foreign_subs,
+            native_subs,
+            out_dir,
+        })
+    }
+
+    /// The video being exported.
+    pub fn video(&self) -> &Video {
+        &self.video
+    }
+
+    /// The foreign-language subtitles being exported.
+    pub fn foreign_subs(&self) -> &SubtitleFile {
+        &self.foreign_subs
+    }
+
+    /// The native-language subtitles being exported, if any.
+    pub fn native_subs(&self) -> Option<&SubtitleFile> {
+        self.native_subs.as_ref()
+    }
+
+    /// The foreign and native cues, aligned by overlapping time period.
+    pub fn aligned_cues(&self) -> Vec<AlignedCue> {
+        align::align_cues(&self.foreign_subs, self.native_subs.as_ref())
+    }
+
+    /// The directory this export is being written to.
+    pub fn out_dir(&self) -> &std::path::Path {
+        &self.out_dir
+    }
+}
+
+fn run_ffmpeg(args: &[&std::ffi::OsStr]) -> Result<()> {
+    let output = Command::new("ffmpeg")
+        .arg("-v")
+        .arg("quiet")
+        .arg("-y")
+        .args(args)
+        .output()
+        .map_err(|e| anyhow::anyhow!("could not run ffmpeg: {}", e))?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "ffmpeg failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    Ok(())
+}
+
+/// Export one Anki-importable CSV file, plus one audio clip per aligned
+/// cue, to `exporter`'s output directory.
+pub fn export_csv(ui: &Ui, exporter: &mut Exporter) -> Result<()> {
+    let aligned = exporter.aligned_cues();
+    let mut csv = String::new();
+    for (i, cue) in aligned.iter().enumerate() {
+        let clip_name = format!("clip-{:04}.mp3", i + 1);
+        let clip_path = exporter.out_dir().join(&clip_name);
+        extract_audio_clip(exporter.video(), cue.period, &clip_path)?;
+        csv.push_str(&format!(
+            "[sound:{}]\t{}\t{}\n",
+            clip_name,
+            cue.foreign.join(" "),
+            cue.native.join(" ")
+        ));
+    }
+    let csv_path = exporter.out_dir().join("cards.csv");
+    fs::write(&csv_path, csv)?;
+    ui.status("export", &format!("wrote {}", csv_path.display()));
+    Ok(())
+}
+
+/// Export an HTML page letting the user review the aligned cues.
+pub fn export_review(ui: &Ui, exporter: &mut Exporter) -> Result<()> {
+    let aligned = exporter.aligned_cues();
+    let mut html = String::from("<!doctype html>\n<html><body><table>\n");
+    for cue in &aligned {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            cue.period,
+            cue.foreign.join("<br>"),
+            cue.native.join("<br>")
+        ));
+    }
+    html.push_str("</table></body></html>\n");
+    let html_path = exporter.out_dir().join("review.html");
+    fs::write(&html_path, html)?;
+    ui.status("export", &format!("wrote {}", html_path.display()));
+    Ok(())
+}
+
+/// Export one audio track per cue in `exporter`'s foreign subtitles.
+pub fn export_tracks(ui: &Ui, exporter: &mut Exporter) -> Result<()> {
+    for (i, cue) in exporter.foreign_subs.cues.clone().iter().enumerate() {
+        let track_name = format!("track-{:04}.mp3", i + 1);
+        let track_path = exporter.out_dir().join(&track_name);
+        extract_audio_clip(exporter.video(), cue.period, &track_path)?;
+    }
+    ui.status(
+        "export",
+        &format!("wrote tracks to {}", exporter.out_dir().display()),
+    );
+    Ok(())
+}
+
+fn extract_audio_clip(video: &Video, period: Period, out_path: &std::path::Path) -> Result<()> {
+    run_ffmpeg(&[
+        std::ffi::OsStr::new("-i"),
+        video.path().as_os_str(),
+        std::ffi::OsStr::new("-ss"),
+        std::ffi::OsStr::new(&period.begin().to_string()),
+        std::ffi::OsStr::new("-to"),
+        std::ffi::OsStr::new(&period.end().to_string()),
+        std::ffi::OsStr::new("-vn"),
+        out_path.as_os_str(),
+    ])
+}
+
+/// Options controlling how [`export_video`] renders a bilingual video.
+#[derive(Debug, Clone, Copy)]
+pub struct VideoExportOptions {
+    /// If true, render only the time spans covered by cues (a
+    /// "condensed" video), instead of the whole file.
+    pub segment: bool,
+    /// Extra time, in seconds, to include before each segment's cues
+    /// start. Only used when `segment` is set.
+    pub lead_in: f64,
+    /// Extra time, in seconds, to include after each segment's cues
+    /// end. Only used when `segment` is set.
+    pub lead_out: f64,
+    /// Length, in seconds, of the fade transition between segments.
+    /// Only used when `segment` is set.
+    pub fade: f64,
+}
+
+impl Default for VideoExportOptions {
+    fn default() -> Self {
+        VideoExportOptions {
+            segment: false,
+            lead_in: 0.5,
+            lead_out: 0.5,
+            fade: 0.2,
+        }
+    }
+}
+
+/// Burn `exporter`'s aligned foreign+native subtitles into a copy of the
+/// video, with the foreign line on top and the native line below. With
+/// `opts.segment` set, only the spans covered by cues are rendered,
+/// cross-faded together into a "condensed" clip.
+pub fn export_video(ui: &Ui, exporter: &mut Exporter, opts: VideoExportOptions) -> Result<PathBuf> {
+    let aligned: Vec<AlignedCue> = exporter
+        .aligned_cues()
+        .into_iter()
+        .filter(|cue| !cue.foreign.is_empty() || !cue.native.is_empty())
+        .collect();
+
+    if !opts.segment {
+        let ass_path = exporter.out_dir().join("bilingual.ass");
+        fs::write(&ass_path, render_bilingual_ass(&aligned, 0.0))?;
+        let out_path = exporter.out_dir().join("bilingual.mp4");
+        burn_subtitles(exporter.video(), &ass_path, None, &out_path)?;
+        ui.status("export", &format!("wrote {}", out_path.display()));
+        return Ok(out_path);
+    }
+
+    let mut clip_paths = vec![];
+    for (i, cue) in aligned.iter().enumerate() {
+        let begin = (cue.period.begin() - opts.lead_in).max(0.0);
+        let end = cue.period.end() + opts.lead_out;
+        let local_period = Period::new(cue.period.begin() - begin, cue.period.end() - begin)?;
+        let local_cue = AlignedCue {
+            period: local_period,
+            foreign: cue.foreign.clone(),
+            native: cue.native.clone(),
+        };
+        let ass_path = exporter.out_dir().join(format!("segment-{:04}.ass", i + 1));
+        fs::write(&ass_path, render_bilingual_ass(&[local_cue], 0.0))?;
+
+        let clip_path = exporter.out_dir().join(format!("segment-{:04}.mp4", i + 1));
+        burn_subtitles(exporter.video(), &ass_path, Some((begin, end)), &clip_path)?;
+        clip_paths.push(clip_path);
+    }
+
+    let out_path = exporter.out_dir().join("condensed.mp4");
+    concat_with_fades(&clip_paths, opts.fade, &out_path)?;
+    ui.status(
+        "export",
+        &format!(
+            "wrote {} ({} segments)",
+            out_path.display(),
+            clip_paths.len()
+        ),
+    );
+    Ok(out_path)
+}
+
+/// Render an ASS subtitle track with each cue emitted as two events: the
+/// foreign line anchored near the top of the frame, and the native line
+/// in the usual bottom position. `base_offset` shifts every timestamp,
+/// for segments whose clock starts partway through the source video.
+fn render_bilingual_ass(cues: &[AlignedCue], base_offset: f64) -> String {
+    let mut out = String::new();
+    out.push_str("[Script Info]\nScriptType: v4.00+\n\n");
+    out.push_str("[V4+ Styles]\n");
+    out.push_str(
+        "Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, \
+         BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, \
+         BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n",
+    );
+    out.push_str(
+        "Style: Foreign,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,\
+         0,1,2,0,8,10,10,10,1\n",
+    );
+    out.push_str(
+        "Style: Native,Arial,20,&H0000FFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,\
+         0,1,2,0,2,10,10,10,1\n\n",
+    );
+    out.push_str("[Events]\n");
+    out.push_str(
+        "Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+    );
+    for cue in cues {
+        let begin = format_ass_timestamp(cue.period.begin() + base_offset);
+        let end = format_ass_timestamp(cue.period.end() + base_offset);
+        if !cue.foreign.is_empty() {
+            out.push_str(&format!(
+                "Dialogue: 0,{},{},Foreign,,0,0,0,,{}\n",
+                begin,
+                end,
+                cue.foreign.join("\\N")
+            ));
+        }
+        if !cue.native.is_empty() {
+            out.push_str(&format!(
+                "Dialogue: 0,{},{},Native,,0,0,0,,{}\n",
+                begin,
+                end,
+                cue.native.join("\\N")
+            ));
+        }
+    }
+    out
+}
+
+fn format_ass_timestamp(secs: f64) -> String {
+    let total_cs = (secs.max(0.0) * 100.0).round() as i64;
+    let cs = total_cs % 100;
+    let total_s = total_cs / 100;
+    let s = total_s % 60;
+    let total_m = total_s / 60;
+    let m = total_m % 60;
+    let h = total_m / 60;
+    format!("{}:{:02}:{:02}.{:02}", h, m, s, cs)
+}
+
+/// Re-encode `video` with `ass_path` burned in via ffmpeg's `ass`
+/// filter, optionally trimming to `[begin, end)` first.
+fn burn_subtitles(
+    video: &Video,
+    ass_path: &std::path::Path,
+    trim: Option<(f64, f64)>,
+    out_path: &std::path::Path,
+) -> Result<()> {
+    let mut args: Vec<std::ffi::OsString> = vec![];
+    if let Some((begin, end)) = trim {
+        args.push("-ss".into());
+        args.push(begin.to_string().into());
+        args.push("-to".into());
+        args.push(end.to_string().into());
+    }
+    args.push("-i".into());
+    args.push(video.path().as_os_str().to_os_string());
+    args.push("-vf".into());
+    args.push(format!("ass={}", escape_for_ffmpeg_filter(ass_path)).into());
+    args.push(out_path.as_os_str().to_os_string());
+    let refs: Vec<&std::ffi::OsStr> = args.iter().map(|a| a.as_os_str()).collect();
+    run_ffmpeg(&refs)
+}
+
+/// Escape a path for use inside an ffmpeg filtergraph argument, where
+/// backslashes, colons, single quotes, commas, and square brackets are
+/// all significant (commas separate filter options and square brackets
+/// delimit link labels, so an ordinary filename containing either would
+/// otherwise break the filtergraph).
+fn escape_for_ffmpeg_filter(path: &std::path::Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace(':', "\\:")
+        .replace('\'', "\\'")
+        .replace(',', "\\,")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+}
+
+/// Concatenate `clips` into `out_path`, crossfading video and audio
+/// between each pair by `fade_secs` seconds.
+fn concat_with_fades(clips: &[PathBuf], fade_secs: f64, out_path: &std::path::Path) -> Result<()> {
+    if clips.is_empty() {
+        return Err(anyhow::anyhow!("no segments to concatenate"));
+    }
+    if clips.len() == 1 || fade_secs <= 0.0 {
+        let list_path = out_path.with_extension("concat.txt");
+        let list = clips
+            .iter()
+            .map(|p| format!("file '{}'\n", p.display()))
+            .collect::<String>();
+        fs::write(&list_path, list)?;
+        run_ffmpeg(&[
+            std::ffi::OsStr::new("-f"),
+            std::ffi::OsStr::new("concat"),
+            std::ffi::OsStr::new("-safe"),
+            std::ffi::OsStr::new("0"),
+            std::ffi::OsStr::new("-i"),
+            list_path.as_os_str(),
+            std::ffi::OsStr::new("-c"),
+            std::ffi::OsStr::new("copy"),
+            out_path.as_os_str(),
+        ])?;
+        fs::remove_file(&list_path).ok();
+        return Ok(());
+    }
+
+    // `xfade`/`acrossfade` need to know each clip's duration up front.
+    let durations: Vec<f64> = clips
+        .iter()
+        .map(|p| probe_duration(p))
+        .collect::<Result<_>>()?;
+
+    let mut args: Vec<std::ffi::OsString> = vec![];
+    for clip in clips {
+        args.push("-i".into());
+        args.push(clip.as_os_str().to_os_string());
+    }
+
+    let mut filter = String::new();
+    let mut video_label = "0:v".to_owned();
+    let mut audio_label = "0:a".to_owned();
+    let mut offset = durations[0];
+    for (i, duration) in durations.iter().enumerate().skip(1) {
+        let next_video = format!("v{}", i);
+        let next_audio = format!("a{}", i);
+        filter.push_str(&format!(
+            "[{}][{}:v]xfade=transition=fade:duration={}:offset={}[{}];",
+            video_label,
+            i,
+            fade_secs,
+            (offset - fade_secs).max(0.0),
+            next_video
+        ));
+        filter.push_str(&format!(
+            "[{}][{}:a]acrossfade=d={}[{}];",
+            audio_label, i, fade_secs, next_audio
+        ));
+        video_label = next_video;
+        audio_label = next_audio;
+        offset += duration - fade_secs;
+    }
+    filter.push_str(&format!(
+        "[{}]null[vout];[{}]anull[aout]",
+        video_label, audio_label
+    ));
+
+    args.push("-filter_complex".into());
+    args.push(filter.into());
+    args.push("-map".into());
+    args.push("[vout]".into());
+    args.push("-map".into());
+    args.push("[aout]".into());
+    args.push(out_path.as_os_str().to_os_string());
+
+    let refs: Vec<&std::ffi::OsStr> = args.iter().map(|a| a.as_os_str()).collect();
+    run_ffmpeg(&refs)
+}
+
+fn probe_duration(path: &std::path::Path) -> Result<f64> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "quiet",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "csv=p=0",
+        ])
+        .arg(path)
+        .output()
+        .map_err(|e| anyhow::anyhow!("could not run ffprobe on {}: {}", path.display(), e))?;
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("could not parse duration of {}: {}", path.display(), e))
+}
@@ -0,0 +1,77 @@
+//! Extracting an embedded subtitle track out of a video container.
+
+use std::process::Command;
+
+use crate::{
+    formats::Format,
+    ui::Ui,
+    video::{Stream, Video},
+    Result,
+};
+
+/// Subtitle codecs that carry rendered bitmaps rather than text, which
+/// `ffmpeg` cannot convert to SubRip or WebVTT.
+const BITMAP_CODECS: &[&str] = &["hdmv_pgs_subtitle", "dvd_subtitle", "dvb_subtitle"];
+
+fn is_bitmap_codec(codec_name: &str) -> bool {
+    BITMAP_CODECS.contains(&codec_name)
+}
+
+/// Extract the subtitle `stream` from `video`, converted to `format`.
+///
+/// Text-based codecs (SubRip, ASS/SSA, `mov_text`, WebVTT, ...) are
+/// converted through `ffmpeg`. Bitmap-based codecs (PGS, VobSub) can't be
+/// converted to text, so this reports a warning via `ui` and returns an
+/// error.
+pub fn extract_subtitle_track(
+    ui: &Ui,
+    video: &Video,
+    stream: &Stream,
+    format: Format,
+) -> Result<String> {
+    if is_bitmap_codec(&stream.codec_name) {
+        ui.status(
+            "extract",
+            &format!(
+                "stream #{} is a bitmap subtitle format ({}) and can't be converted to text",
+                stream.index, stream.codec_name
+            ),
+        );
+        return Err(anyhow::anyhow!(
+            "subtitle stream #{} uses the bitmap codec {:?}, which can't be extracted as text",
+            stream.index,
+            stream.codec_name
+        ));
+    }
+
+    let format_name = match format {
+        Format::Srt => "srt",
+        Format::Vtt => "webvtt",
+        Format::Ass => "ass",
+    };
+    let output = Command::new("ffmpeg")
+        .args(["-v", "quiet", "-i"])
+        .arg(video.path())
+        .arg("-map")
+        .arg(format!("0:{}", stream.index))
+        .args(["-c:s", format_name, "-f", format_name, "-"])
+        .output()
+        .map_err(|e| {
+            anyhow::anyhow!("could not run ffmpeg on {}: {}", video.path().display(), e)
+        })?;
+    if !output.status.success() {
+        return Err(anyhow::anyhow!(
+            "ffmpeg failed to extract subtitle stream #{} from {}: {}",
+            stream.index,
+            video.path().display(),
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    String::from_utf8(output.stdout).map_err(|e| {
+        anyhow::anyhow!(
+            "subtitle stream #{} was not valid UTF-8: {}",
+            stream.index,
+            e
+        )
+    })
+}
@@ -0,0 +1,336 @@
+//! Reading and writing subtitle formats other than SubRip.
+//!
+//! [`crate::srt::SubtitleFile`] remains the canonical in-memory
+//! representation used throughout the tool; this module only knows how
+//! to detect a file's format and convert it to and from that
+//! representation, so the rest of the codebase never has to care whether
+//! a subtitle file on disk was SubRip, WebVTT, or ASS/SSA.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::{
+    srt::{Cue, SubtitleFile},
+    time::Period,
+    Result,
+};
+
+/// A subtitle file format we know how to read or write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "lower")]
+pub enum Format {
+    /// SubRip (`.srt`).
+    Srt,
+    /// WebVTT (`.vtt`).
+    Vtt,
+    /// Advanced SubStation Alpha / SubStation Alpha (`.ass` / `.ssa`).
+    Ass,
+}
+
+impl Format {
+    /// Guess a subtitle file's format from its extension, falling back
+    /// to sniffing its contents if the extension is missing or
+    /// unrecognized.
+    pub fn detect(path: &Path) -> Result<Format> {
+        let by_extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .and_then(|ext| match ext.as_str() {
+                "srt" => Some(Format::Srt),
+                "vtt" => Some(Format::Vtt),
+                "ass" | "ssa" => Some(Format::Ass),
+                _ => None,
+            });
+        if let Some(format) = by_extension {
+            return Ok(format);
+        }
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("could not read {}: {}", path.display(), e))?;
+        Ok(Format::sniff(&data))
+    }
+
+    /// Guess a format from a file's contents alone, defaulting to SubRip
+    /// if nothing more specific matches.
+    pub fn sniff(data: &str) -> Format {
+        let head = data.trim_start();
+        if head.starts_with("WEBVTT") {
+            Format::Vtt
+        } else if head.starts_with("[Script Info]") {
+            Format::Ass
+        } else {
+            Format::Srt
+        }
+    }
+
+    /// Load and parse a subtitle file from disk, using `format` if given
+    /// or auto-detecting it otherwise.
+    pub fn read_path(path: &Path, format: Option<Format>) -> Result<SubtitleFile> {
+        let format = match format {
+            Some(format) => format,
+            None => Format::detect(path)?,
+        };
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("could not read {}: {}", path.display(), e))?;
+        format.parse(&data)
+    }
+
+    /// Parse `data` as a subtitle file in this format.
+    pub fn parse(&self, data: &str) -> Result<SubtitleFile> {
+        match self {
+            Format::Srt => SubtitleFile::parse_str(data),
+            Format::Vtt => parse_vtt(data),
+            Format::Ass => parse_ass(data),
+        }
+    }
+
+    /// Serialize `file` into this format.
+    pub fn write(&self, file: &SubtitleFile) -> String {
+        match self {
+            Format::Srt => file.to_string(),
+            Format::Vtt => write_vtt(file),
+            Format::Ass => write_ass(file),
+        }
+    }
+}
+
+/// Parse an `hh:mm:ss` or `mm:ss` timestamp whose fractional seconds are
+/// separated by `decimal_sep`, as used by WebVTT (`.`) and ASS (`.`).
+fn parse_hms(ts: &str, decimal_sep: char) -> Result<f64> {
+    let ts = ts.replace(decimal_sep, ".");
+    let parts: Vec<&str> = ts.split(':').collect();
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [h, m, s] => (h.parse::<f64>()?, m.parse::<f64>()?, s.parse::<f64>()?),
+        [m, s] => (0.0, m.parse::<f64>()?, s.parse::<f64>()?),
+        _ => return Err(anyhow::anyhow!("malformed timestamp: {:?}", ts)),
+    };
+    Ok(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+fn parse_vtt(data: &str) -> Result<SubtitleFile> {
+    let mut cues = vec![];
+    let mut index = 0;
+    for block in data.replace("\r\n", "\n").split("\n\n") {
+        let block = block.trim();
+        if block.is_empty() || block.starts_with("WEBVTT") || block.starts_with("NOTE") {
+            continue;
+        }
+        let mut lines = block.lines();
+        let mut line = lines
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("expected a VTT cue"))?;
+        if !line.contains("-->") {
+            // Skip an optional cue identifier line.
+            line = lines
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("expected a VTT timing line"))?;
+        }
+        let (begin, end) = parse_vtt_timing(line)?;
+        let text = lines.map(|l| l.to_owned()).collect();
+        index += 1;
+        cues.push(Cue::new(index, Period::new(begin, end)?, text));
+    }
+    Ok(SubtitleFile::new(cues))
+}
+
+fn parse_vtt_timing(line: &str) -> Result<(f64, f64)> {
+    let mut parts = line.splitn(2, "-->");
+    let begin = parts
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("malformed VTT timing line: {:?}", line))?;
+    let end = parts
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("malformed VTT timing line: {:?}", line))?;
+    // The end timestamp may be followed by cue settings, e.g. `line:90%`.
+    let end = end.split_whitespace().next().unwrap_or(end);
+    Ok((parse_hms(begin.trim(), '.')?, parse_hms(end, '.')?))
+}
+
+fn format_vtt_timestamp(secs: f64) -> String {
+    let total_ms = (secs * 1000.0).round() as i64;
+    let ms = total_ms % 1000;
+    let total_s = total_ms / 1000;
+    let s = total_s % 60;
+    let total_m = total_s / 60;
+    let m = total_m % 60;
+    let h = total_m / 60;
+    format!("{:02}:{:02}:{:02}.{:03}", h, m, s, ms)
+}
+
+fn write_vtt(file: &SubtitleFile) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in &file.cues {
+        out.push_str(&format!(
+            "{} --> {}\n",
+            format_vtt_timestamp(cue.period.begin()),
+            format_vtt_timestamp(cue.period.end())
+        ));
+        for line in &cue.lines {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The `[Events]` field order we expect, as declared by an ASS file's own
+/// `Format:` line (field names aren't fixed, so we look up the ones we
+/// care about by name rather than assuming a position).
+struct AssEventFields {
+    start: usize,
+    end: usize,
+    text: usize,
+}
+
+fn parse_ass(data: &str) -> Result<SubtitleFile> {
+    let mut in_events = false;
+    let mut fields: Option<AssEventFields> = None;
+    let mut cues = vec![];
+    let mut index = 0;
+
+    for line in data.lines() {
+        let line = line.trim();
+        if line.eq_ignore_ascii_case("[events]") {
+            in_events = true;
+            continue;
+        } else if line.starts_with('[') {
+            in_events = false;
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+
+        if let Some(rest) = line.strip_prefix("Format:") {
+            let names: Vec<String> = rest.split(',').map(|f| f.trim().to_lowercase()).collect();
+            let find = |name: &str| {
+                names
+                    .iter()
+                    .position(|f| f == name)
+                    .ok_or_else(|| anyhow::anyhow!("[Events] Format: line has no {} field", name))
+            };
+            fields = Some(AssEventFields {
+                start: find("start")?,
+                end: find("end")?,
+                text: find("text")?,
+            });
+        } else if let Some(rest) = line.strip_prefix("Dialogue:") {
+            let fields = fields
+                .as_ref()
+                .ok_or_else(|| anyhow::anyhow!("Dialogue: line appeared before Format: line"))?;
+            // `Text` is the last field and may itself contain commas, so
+            // only split off the fixed-width fields that precede it.
+            let parts: Vec<&str> = rest.splitn(fields.text + 1, ',').collect();
+            if parts.len() <= fields.text {
+                continue;
+            }
+            let begin = parse_ass_timestamp(parts[fields.start].trim())?;
+            let end = parse_ass_timestamp(parts[fields.end].trim())?;
+            let lines = parts[fields.text]
+                .replace("\\N", "\n")
+                .replace("\\n", "\n")
+                .lines()
+                .map(|l| l.to_owned())
+                .collect();
+            index += 1;
+            cues.push(Cue::new(index, Period::new(begin, end)?, lines));
+        }
+    }
+    Ok(SubtitleFile::new(cues))
+}
+
+fn parse_ass_timestamp(ts: &str) -> Result<f64> {
+    parse_hms(ts, '.')
+}
+
+fn format_ass_timestamp(secs: f64) -> String {
+    let total_cs = (secs * 100.0).round() as i64;
+    let cs = total_cs % 100;
+    let total_s = total_cs / 100;
+    let s = total_s % 60;
+    let total_m = total_s / 60;
+    let m = total_m % 60;
+    let h = total_m / 60;
+    format!("{}:{:02}:{:02}.{:02}", h, m, s, cs)
+}
+
+fn write_ass(file: &SubtitleFile) -> String {
+    let mut out = String::new();
+    out.push_str("[Script Info]\nScriptType: v4.00+\n\n");
+    out.push_str("[V4+ Styles]\n");
+    out.push_str(
+        "Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, \
+         BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, \
+         BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n",
+    );
+    out.push_str(
+        "Style: Default,Arial,20,&H00FFFFFF,&H000000FF,&H00000000,&H00000000,0,0,0,0,100,100,0,\
+         0,1,2,0,2,10,10,10,1\n\n",
+    );
+    out.push_str("[Events]\n");
+    out.push_str(
+        "Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+    );
+    for cue in &file.cues {
+        out.push_str(&format!(
+            "Dialogue: 0,{},{},Default,,0,0,0,,{}\n",
+            format_ass_timestamp(cue.period.begin()),
+            format_ass_timestamp(cue.period.end()),
+            cue.lines.join("\\N")
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_vtt_timings_with_trailing_cue_settings() {
+        let data = "WEBVTT\n\n00:00:01.000 --> 00:00:02.500 line:90%\nHello\n";
+        let file = parse_vtt(data).unwrap();
+        assert_eq!(file.cues.len(), 1);
+        assert_eq!(file.cues[0].period.begin(), 1.0);
+        assert_eq!(file.cues[0].period.end(), 2.5);
+        assert_eq!(file.cues[0].lines, vec!["Hello".to_string()]);
+    }
+
+    #[test]
+    fn vtt_round_trips_through_write_and_parse() {
+        let srt = SubtitleFile::parse_str(
+            "1\n00:00:01,000 --> 00:00:02,000\nHello\n\n2\n00:00:03,000 --> 00:00:04,000\nWorld\n",
+        )
+        .unwrap();
+        let vtt = write_vtt(&srt);
+        let reparsed = parse_vtt(&vtt).unwrap();
+        assert_eq!(reparsed.cues.len(), srt.cues.len());
+        assert_eq!(reparsed.cues[0].period, srt.cues[0].period);
+        assert_eq!(reparsed.cues[1].lines, vec!["World".to_string()]);
+    }
+
+    #[test]
+    fn parses_ass_dialogue_lines_and_strips_newline_tags() {
+        let data = "[Script Info]\n\
+                     [Events]\n\
+                     Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+                     Dialogue: 0,0:00:01.00,0:00:02.50,Default,,0,0,0,,Hello\\Nworld\n";
+        let file = parse_ass(data).unwrap();
+        assert_eq!(file.cues.len(), 1);
+        assert_eq!(file.cues[0].period.begin(), 1.0);
+        assert_eq!(file.cues[0].period.end(), 2.5);
+        assert_eq!(
+            file.cues[0].lines,
+            vec!["Hello".to_string(), "world".to_string()]
+        );
+    }
+
+    #[test]
+    fn sniff_detects_format_from_header() {
+        assert_eq!(Format::sniff("WEBVTT\n\n..."), Format::Vtt);
+        assert_eq!(Format::sniff("[Script Info]\n..."), Format::Ass);
+        assert_eq!(Format::sniff("1\n00:00:01,000 --> 00:00:02,000\n"), Format::Srt);
+    }
+}
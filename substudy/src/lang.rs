@@ -0,0 +1,34 @@
+//! Language codes used to tag subtitle tracks and translation targets.
+
+use std::fmt;
+
+use crate::Result;
+
+/// An ISO 639-1 language code, such as `en` or `ja`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Lang(String);
+
+impl Lang {
+    /// Parse an ISO 639-1 language code.
+    pub fn iso639(code: &str) -> Result<Lang> {
+        let code = code.trim().to_lowercase();
+        if code.len() != 2 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(anyhow::anyhow!(
+                "not a valid ISO 639-1 language code: {:?}",
+                code
+            ));
+        }
+        Ok(Lang(code))
+    }
+
+    /// This code as a plain string, e.g. `"en"`.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
@@ -13,14 +13,16 @@ use video::Video;
 
 use crate::{
     align::combine_files,
+    formats::Format,
     import::{import_whisper_json, WhisperJson},
     lang::Lang,
     services::oai::{
-        transcribe_subtitles_to_srt_file, transcribe_subtitles_to_whisper_json,
-        translate_subtitle_file, TranscriptionFormat,
+        detect_language, transcribe_subtitles_to_srt_file, transcribe_subtitles_to_whisper_json,
+        translate_subtitle_files, TranscriptionFormat,
     },
     srt::SubtitleFile,
     ui::Ui,
+    video::CodecType,
 };
 
 pub mod align;
@@ -29,12 +31,15 @@ pub mod contexts;
 pub mod decode;
 pub mod errors;
 pub mod export;
+pub mod extract;
+pub mod formats;
 pub mod import;
 pub mod lang;
 pub mod merge;
 pub mod segment;
 pub mod services;
 pub mod srt;
+pub mod sync;
 pub mod time;
 pub mod ui;
 mod vad;
@@ -51,6 +56,11 @@ enum Args {
     Clean {
         /// Path to the subtitle file to clean.
         subs: PathBuf,
+
+        /// The subtitle file's format. Auto-detected from the file
+        /// extension and contents if not given.
+        #[arg(long)]
+        format: Option<Format>,
     },
 
     /// Combine two subtitle files into a single bilingual subtitle file.
@@ -61,6 +71,27 @@ enum Args {
 
         /// Path to the native language subtitle file to be combined.
         native_subs: PathBuf,
+
+        /// The format of both subtitle files. Auto-detected from each
+        /// file's extension and contents if not given.
+        #[arg(long)]
+        format: Option<Format>,
+    },
+
+    /// Convert a subtitle file from one format to another.
+    #[command(name = "convert")]
+    Convert {
+        /// Path to the subtitle file to convert.
+        input: PathBuf,
+
+        /// The input file's format. Auto-detected from its extension and
+        /// contents if not given.
+        #[arg(long)]
+        from: Option<Format>,
+
+        /// The format to convert to.
+        #[arg(long)]
+        to: Format,
     },
 
     /// Export subtitles in one of several formats (Anki cards, music tracks,
@@ -71,6 +102,27 @@ enum Args {
         format: ExportFormat,
     },
 
+    /// Extract an embedded subtitle track from a video container.
+    #[command(name = "extract")]
+    Extract {
+        /// Path to the video.
+        video: PathBuf,
+
+        /// Index of the subtitle stream to extract, as shown by `list
+        /// tracks`. Required if the video has more than one subtitle
+        /// stream and `--lang` isn't given.
+        #[arg(long)]
+        track: Option<usize>,
+
+        /// Language code of the subtitle stream to extract (e.g. "en").
+        #[arg(long)]
+        lang: Option<String>,
+
+        /// The format to write the extracted subtitles in.
+        #[arg(long, default_value = "srt")]
+        format: Format,
+    },
+
     /// Import subtitles from one of several formats (Whisper JSON, etc).
     #[command(name = "import")]
     Import {
@@ -85,6 +137,17 @@ enum Args {
         to_list: ToList,
     },
 
+    /// Resync a subtitle file to match the speech in a video's audio
+    /// track.
+    #[command(name = "sync")]
+    Sync {
+        /// Path to the video containing the reference audio.
+        video: PathBuf,
+
+        /// Path to the out-of-sync subtitle file.
+        subs: PathBuf,
+    },
+
     /// Transcribe subtitles from audio.
     #[command(name = "transcribe")]
     Transcribe {
@@ -98,17 +161,30 @@ enum Args {
         /// Output format for the transcription.
         #[arg(long, default_value = "srt")]
         format: TranscriptionFormat,
+
+        /// Treat the video's audio as this language instead of
+        /// auto-detecting it.
+        #[arg(long)]
+        treat_undefined_as: Option<String>,
     },
 
-    /// Translate subtitles.
+    /// Translate subtitles, optionally into more than one language at
+    /// once.
     #[command(name = "translate")]
     Translate {
         /// Path to the subtitle file to translate.
         foreign_subs: PathBuf,
 
-        /// Target language code (e.g. "en" for English).
+        /// Target language code(s), e.g. "en" or "en,de,es". One output
+        /// file is written per language, next to the input and named
+        /// after its file stem: `<stem>.<lang>.srt`.
         #[arg(long)]
         native_lang: String,
+
+        /// The subtitle file's format. Auto-detected from the file
+        /// extension and contents if not given.
+        #[arg(long)]
+        format: Option<Format>,
     },
 }
 
@@ -149,6 +225,40 @@ enum ExportFormat {
         /// Path to the file containing foreign language subtitles.
         foreign_subs: PathBuf,
     },
+
+    /// Export as a hardsubbed video with the foreign line on top and the
+    /// native line below, burned into the picture.
+    #[command(name = "video")]
+    Video {
+        /// Path to the video.
+        video: PathBuf,
+
+        /// Path to the file containing foreign language subtitles.
+        foreign_subs: PathBuf,
+
+        /// Path to the file containing native language subtitles.
+        native_subs: Option<PathBuf>,
+
+        /// Only render the time spans covered by cues, cross-faded
+        /// together into a condensed clip, instead of the whole video.
+        #[arg(long)]
+        segment: bool,
+
+        /// Extra time, in seconds, to include before each segment's cues
+        /// start. Only used with `--segment`.
+        #[arg(long, default_value = "0.5")]
+        lead_in: f64,
+
+        /// Extra time, in seconds, to include after each segment's cues
+        /// end. Only used with `--segment`.
+        #[arg(long, default_value = "0.5")]
+        lead_out: f64,
+
+        /// Length, in seconds, of the fade transition between segments.
+        /// Only used with `--segment`.
+        #[arg(long, default_value = "0.2")]
+        fade: f64,
+    },
 }
 
 impl ExportFormat {
@@ -158,6 +268,7 @@ impl ExportFormat {
             ExportFormat::Csv { .. } => "csv",
             ExportFormat::Review { .. } => "review",
             ExportFormat::Tracks { .. } => "tracks",
+            ExportFormat::Video { .. } => "video",
         }
     }
 
@@ -167,6 +278,7 @@ impl ExportFormat {
             ExportFormat::Csv { ref video, .. } => &video,
             ExportFormat::Review { ref video, .. } => &video,
             ExportFormat::Tracks { ref video, .. } => &video,
+            ExportFormat::Video { ref video, .. } => &video,
         }
     }
 
@@ -182,6 +294,9 @@ impl ExportFormat {
             ExportFormat::Tracks {
                 ref foreign_subs, ..
             } => &foreign_subs,
+            ExportFormat::Video {
+                ref foreign_subs, ..
+            } => &foreign_subs,
         }
     }
 
@@ -195,6 +310,29 @@ impl ExportFormat {
                 ref native_subs, ..
             } => native_subs.as_ref().map(|p| p.as_path()),
             ExportFormat::Tracks { .. } => None,
+            ExportFormat::Video {
+                ref native_subs, ..
+            } => native_subs.as_ref().map(|p| p.as_path()),
+        }
+    }
+
+    /// Get the options controlling a `video` export. Meaningless for
+    /// other export kinds.
+    fn video_options(&self) -> export::VideoExportOptions {
+        match *self {
+            ExportFormat::Video {
+                segment,
+                lead_in,
+                lead_out,
+                fade,
+                ..
+            } => export::VideoExportOptions {
+                segment,
+                lead_in,
+                lead_out,
+                fade,
+            },
+            _ => export::VideoExportOptions::default(),
         }
     }
 }
@@ -217,6 +355,11 @@ enum ToList {
     Tracks {
         /// Path to the video.
         video: PathBuf,
+
+        /// Treat an audio stream with no language tag as this language
+        /// instead of auto-detecting it.
+        #[arg(long)]
+        treat_undefined_as: Option<String>,
     },
 }
 
@@ -229,11 +372,15 @@ async fn main() -> Result<()> {
     // Parse our command-line arguments using docopt (very shiny).
     let args: Args = Args::parse();
     match args {
-        Args::Clean { subs } => spawn_blocking(move || cmd_clean(&subs)).await?,
+        Args::Clean { subs, format } => spawn_blocking(move || cmd_clean(&subs, format)).await?,
         Args::Combine {
             foreign_subs,
             native_subs,
-        } => spawn_blocking(move || cmd_combine(&foreign_subs, &native_subs)).await?,
+            format,
+        } => spawn_blocking(move || cmd_combine(&foreign_subs, &native_subs, format)).await?,
+        Args::Convert { input, from, to } => {
+            spawn_blocking(move || cmd_convert(&input, from, to)).await?
+        }
         Args::Export { format } => {
             let ui = ui.clone();
             spawn_blocking(move || {
@@ -243,43 +390,104 @@ async fn main() -> Result<()> {
                     format.video(),
                     format.foreign_subs(),
                     format.native_subs(),
+                    format.video_options(),
                 )
             })
             .await?
         }
+        Args::Extract {
+            video,
+            track,
+            lang,
+            format,
+        } => {
+            let ui = ui.clone();
+            spawn_blocking(move || cmd_extract(&ui, &video, track, lang.as_deref(), format)).await?
+        }
         Args::Import { format } => spawn_blocking(move || cmd_import(format)).await?,
         Args::List {
-            to_list: ToList::Tracks { video },
-        } => spawn_blocking(move || cmd_tracks(&video)).await?,
+            to_list:
+                ToList::Tracks {
+                    video,
+                    treat_undefined_as,
+                },
+        } => cmd_tracks(&ui, &video, treat_undefined_as.as_deref()).await,
+        Args::Sync { video, subs } => {
+            let ui = ui.clone();
+            spawn_blocking(move || cmd_sync(&ui, &video, &subs)).await?
+        }
         Args::Transcribe {
             video,
             example_text,
             format,
-        } => cmd_transcribe(&ui, &video, &example_text, format).await,
+            treat_undefined_as,
+        } => cmd_transcribe(&ui, &video, &example_text, format, treat_undefined_as.as_deref()).await,
         Args::Translate {
             foreign_subs,
             native_lang,
-        } => cmd_translate(&ui, &foreign_subs, &native_lang).await,
+            format,
+        } => cmd_translate(&ui, &foreign_subs, &native_lang, format).await,
     }
 }
 
-fn cmd_clean(path: &Path) -> Result<()> {
-    let file1 = SubtitleFile::cleaned_from_path(path)?;
+fn cmd_clean(path: &Path, format: Option<Format>) -> Result<()> {
+    let file1 = SubtitleFile::cleaned_from_path_as(path, format)?;
     print!("{}", file1.to_string());
     Ok(())
 }
 
-fn cmd_combine(path1: &Path, path2: &Path) -> Result<()> {
-    let file1 = SubtitleFile::cleaned_from_path(path1)?;
-    let file2 = SubtitleFile::cleaned_from_path(path2)?;
+fn cmd_combine(path1: &Path, path2: &Path, format: Option<Format>) -> Result<()> {
+    let file1 = SubtitleFile::cleaned_from_path_as(path1, format)?;
+    let file2 = SubtitleFile::cleaned_from_path_as(path2, format)?;
     print!("{}", combine_files(&file1, &file2).to_string());
     Ok(())
 }
 
-fn cmd_tracks(path: &Path) -> Result<()> {
+fn cmd_convert(path: &Path, from: Option<Format>, to: Format) -> Result<()> {
+    let file = Format::read_path(path, from)?;
+    print!("{}", to.write(&file));
+    Ok(())
+}
+
+fn cmd_extract(
+    ui: &Ui,
+    video_path: &Path,
+    track: Option<usize>,
+    lang: Option<&str>,
+    format: Format,
+) -> Result<()> {
+    let video = Video::new(video_path)?;
+    let lang = lang.map(Lang::iso639).transpose()?;
+    let stream = video.subtitle_stream(track, lang.as_ref())?;
+    let text = extract::extract_subtitle_track(ui, &video, &stream, format)?;
+    print!("{}", text);
+    Ok(())
+}
+
+async fn cmd_tracks(ui: &Ui, path: &Path, treat_undefined_as: Option<&str>) -> Result<()> {
+    let treat_undefined_as = treat_undefined_as.map(Lang::iso639).transpose()?;
     let v = Video::new(path)?;
+
+    // Only bother detecting the language once, even if several audio
+    // streams are missing a tag.
+    let mut detection_attempted = false;
+    let mut detected: Option<Lang> = None;
     for stream in v.streams() {
-        let lang = stream.language();
+        let lang = match stream.language() {
+            Some(lang) => Some(lang),
+            None if stream.codec_type == CodecType::Audio => {
+                if let Some(lang) = &treat_undefined_as {
+                    Some(lang.clone())
+                } else {
+                    if !detection_attempted {
+                        detection_attempted = true;
+                        detected = detect_language(ui, &v).await?.lang;
+                    }
+                    detected.clone()
+                }
+            }
+            None => None,
+        };
         let lang_str = lang
             .map(|l| l.as_str().to_owned())
             .unwrap_or("??".to_owned());
@@ -288,12 +496,21 @@ fn cmd_tracks(path: &Path) -> Result<()> {
     Ok(())
 }
 
+fn cmd_sync(ui: &Ui, video_path: &Path, subs_path: &Path) -> Result<()> {
+    let video = Video::new(video_path)?;
+    let subs = SubtitleFile::cleaned_from_path(subs_path)?;
+    let synced = sync::sync_subtitle_file(ui, &video, &subs)?;
+    print!("{}", synced.to_string());
+    Ok(())
+}
+
 fn cmd_export(
     ui: &Ui,
     kind: &str,
     video_path: &Path,
     foreign_sub_path: &Path,
     native_sub_path: Option<&Path>,
+    video_options: export::VideoExportOptions,
 ) -> Result<()> {
     // Load our input files.
     let video = Video::new(video_path)?;
@@ -308,6 +525,9 @@ fn cmd_export(
         "csv" => export::export_csv(ui, &mut exporter)?,
         "review" => export::export_review(ui, &mut exporter)?,
         "tracks" => export::export_tracks(ui, &mut exporter)?,
+        "video" => {
+            export::export_video(ui, &mut exporter, video_options)?;
+        }
         _ => panic!("Uknown export type: {}", kind),
     }
 
@@ -330,27 +550,51 @@ async fn cmd_transcribe(
     video: &Path,
     example_text: &Path,
     format: TranscriptionFormat,
+    treat_undefined_as: Option<&str>,
 ) -> Result<()> {
     let v = Video::new(video)?;
     let text = std::fs::read_to_string(example_text)?;
+    let lang = match treat_undefined_as {
+        Some(code) => Some(Lang::iso639(code)?),
+        // If Whisper detected a language we don't have an ISO 639-1 code
+        // for, fall back to letting Whisper auto-detect during the
+        // transcription request itself rather than failing outright.
+        None => detect_language(ui, &v).await?.lang,
+    };
     match format {
         TranscriptionFormat::WhisperJson => {
-            let json = transcribe_subtitles_to_whisper_json(ui, &v, &text).await?;
+            let json = transcribe_subtitles_to_whisper_json(ui, &v, &text, lang.as_ref()).await?;
             let json_str = serde_json::to_string_pretty(&json)?;
             print!("{}", json_str);
         }
         TranscriptionFormat::Srt => {
-            let srt = transcribe_subtitles_to_srt_file(ui, &v, &text).await?;
+            let srt = transcribe_subtitles_to_srt_file(ui, &v, &text, lang.as_ref()).await?;
             print!("{}", srt.to_string());
         }
     }
     Ok(())
 }
 
-async fn cmd_translate(ui: &Ui, foreign_subs: &Path, native_lang: &str) -> Result<()> {
-    let file = SubtitleFile::cleaned_from_path(foreign_subs)?;
-    let native_lang = Lang::iso639(native_lang)?;
-    let translated = translate_subtitle_file(ui, &file, native_lang).await?;
-    print!("{}", translated.to_string());
+async fn cmd_translate(
+    ui: &Ui,
+    foreign_subs: &Path,
+    native_langs: &str,
+    format: Option<Format>,
+) -> Result<()> {
+    let file = SubtitleFile::cleaned_from_path_as(foreign_subs, format)?;
+    let langs = native_langs
+        .split(',')
+        .map(|code| Lang::iso639(code.trim()))
+        .collect::<Result<Vec<_>>>()?;
+    let translated = translate_subtitle_files(ui, &file, &langs).await?;
+    let stem = foreign_subs
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("translated");
+    for (lang, subs) in translated {
+        let out_path = foreign_subs.with_file_name(format!("{}.{}.srt", stem, lang));
+        std::fs::write(&out_path, subs.to_string())?;
+        ui.status("translate", &format!("wrote {}", out_path.display()));
+    }
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,4 @@
+//! Integrations with external services used to process subtitles and
+//! audio.
+
+pub mod oai;
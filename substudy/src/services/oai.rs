@@ -0,0 +1,582 @@
+//! Transcription and translation backed by OpenAI's APIs.
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use clap::ValueEnum;
+use tokio::sync::Semaphore;
+
+use crate::{
+    lang::Lang,
+    srt::{Cue, SubtitleFile},
+    time::Period,
+    ui::Ui,
+    video::Video,
+    Result,
+};
+
+/// The sample rate, in Hz, at which we extract audio to send to Whisper.
+const AUDIO_SAMPLE_RATE: u32 = 16_000;
+
+/// How many translation requests we'll have in flight at once. OpenAI
+/// rate-limits per account, not per request, so a handful of concurrent
+/// requests is enough to keep the pipe full without tripping the limit.
+const MAX_CONCURRENT_REQUESTS: usize = 4;
+
+/// How many times we'll retry a single translation request after a
+/// rate-limit or server error before giving up on it.
+const MAX_RETRIES: u32 = 5;
+
+/// How much audio, in seconds, we sample when detecting a video's spoken
+/// language. Whisper's language identification works well on short clips,
+/// so we don't need to transcribe the whole file.
+const LANGUAGE_DETECTION_SAMPLE_SECS: f64 = 30.0;
+
+/// The language names Whisper reports in its `language` field, mapped to
+/// the ISO 639-1 codes `substudy` uses everywhere else. This covers
+/// every language in Whisper's own `LANGUAGES` table that has a
+/// two-letter ISO 639-1 code; a handful of Whisper languages (e.g.
+/// Cantonese, Hawaiian) don't and are intentionally left unmapped, since
+/// there's no 639-1 code to map them to.
+const WHISPER_LANGUAGE_CODES: &[(&str, &str)] = &[
+    ("english", "en"),
+    ("chinese", "zh"),
+    ("german", "de"),
+    ("spanish", "es"),
+    ("russian", "ru"),
+    ("korean", "ko"),
+    ("french", "fr"),
+    ("japanese", "ja"),
+    ("portuguese", "pt"),
+    ("turkish", "tr"),
+    ("polish", "pl"),
+    ("catalan", "ca"),
+    ("dutch", "nl"),
+    ("arabic", "ar"),
+    ("swedish", "sv"),
+    ("italian", "it"),
+    ("indonesian", "id"),
+    ("hindi", "hi"),
+    ("finnish", "fi"),
+    ("vietnamese", "vi"),
+    ("hebrew", "he"),
+    ("ukrainian", "uk"),
+    ("greek", "el"),
+    ("malay", "ms"),
+    ("czech", "cs"),
+    ("romanian", "ro"),
+    ("danish", "da"),
+    ("hungarian", "hu"),
+    ("tamil", "ta"),
+    ("norwegian", "no"),
+    ("thai", "th"),
+    ("urdu", "ur"),
+    ("croatian", "hr"),
+    ("bulgarian", "bg"),
+    ("lithuanian", "lt"),
+    ("latin", "la"),
+    ("maori", "mi"),
+    ("malayalam", "ml"),
+    ("welsh", "cy"),
+    ("slovak", "sk"),
+    ("telugu", "te"),
+    ("persian", "fa"),
+    ("latvian", "lv"),
+    ("bengali", "bn"),
+    ("serbian", "sr"),
+    ("azerbaijani", "az"),
+    ("slovenian", "sl"),
+    ("kannada", "kn"),
+    ("estonian", "et"),
+    ("macedonian", "mk"),
+    ("breton", "br"),
+    ("basque", "eu"),
+    ("icelandic", "is"),
+    ("armenian", "hy"),
+    ("nepali", "ne"),
+    ("mongolian", "mn"),
+    ("bosnian", "bs"),
+    ("kazakh", "kk"),
+    ("albanian", "sq"),
+    ("swahili", "sw"),
+    ("galician", "gl"),
+    ("marathi", "mr"),
+    ("punjabi", "pa"),
+    ("sinhala", "si"),
+    ("khmer", "km"),
+    ("shona", "sn"),
+    ("yoruba", "yo"),
+    ("somali", "so"),
+    ("afrikaans", "af"),
+    ("occitan", "oc"),
+    ("georgian", "ka"),
+    ("belarusian", "be"),
+    ("tajik", "tg"),
+    ("sindhi", "sd"),
+    ("gujarati", "gu"),
+    ("amharic", "am"),
+    ("yiddish", "yi"),
+    ("lao", "lo"),
+    ("uzbek", "uz"),
+    ("faroese", "fo"),
+    ("haitian creole", "ht"),
+    ("pashto", "ps"),
+    ("turkmen", "tk"),
+    ("nynorsk", "nn"),
+    ("maltese", "mt"),
+    ("sanskrit", "sa"),
+    ("luxembourgish", "lb"),
+    ("myanmar", "my"),
+    ("tibetan", "bo"),
+    ("tagalog", "tl"),
+    ("malagasy", "mg"),
+    ("assamese", "as"),
+    ("tatar", "tt"),
+    ("lingala", "ln"),
+    ("hausa", "ha"),
+    ("bashkir", "ba"),
+    ("javanese", "jw"),
+    ("sundanese", "su"),
+];
+
+/// Output format for the `transcribe` command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "kebab-case")]
+pub enum TranscriptionFormat {
+    /// Whisper's native verbose JSON format, with word-level timing.
+    WhisperJson,
+    /// SubRip (`.srt`).
+    Srt,
+}
+
+/// Ask Whisper to transcribe `video`'s audio track, using `example_text`
+/// as a prompt to bias its vocabulary and spelling, and return its raw
+/// verbose-JSON response. `lang`, if given, is passed to Whisper as a
+/// hint for the spoken language; otherwise Whisper detects it itself.
+pub async fn transcribe_subtitles_to_whisper_json(
+    ui: &Ui,
+    video: &Video,
+    example_text: &str,
+    lang: Option<&Lang>,
+) -> Result<serde_json::Value> {
+    ui.status(
+        "transcribe",
+        &format!("extracting audio from {}", video.path().display()),
+    );
+    let audio_path = extract_audio_file(video.path(), None)?;
+    let audio = fs::read(&audio_path);
+    let _ = fs::remove_file(&audio_path);
+    let audio = audio?;
+
+    let api_key = env::var("OPENAI_API_KEY")
+        .map_err(|_| anyhow::anyhow!("OPENAI_API_KEY must be set to use Whisper transcription"))?;
+    let mut form = reqwest::multipart::Form::new()
+        .text("model", "whisper-1")
+        .text("prompt", example_text.to_owned())
+        .text("response_format", "verbose_json")
+        .part(
+            "file",
+            reqwest::multipart::Part::bytes(audio)
+                .file_name("audio.wav")
+                .mime_str("audio/wav")?,
+        );
+    if let Some(lang) = lang {
+        form = form.text("language", lang.as_str().to_owned());
+    }
+
+    ui.status("transcribe", "sending audio to Whisper");
+    let response = reqwest::Client::new()
+        .post("https://api.openai.com/v1/audio/transcriptions")
+        .bearer_auth(api_key)
+        .multipart(form)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.json().await?)
+}
+
+/// Like [`transcribe_subtitles_to_whisper_json`], but converts the result
+/// directly to a [`SubtitleFile`] using each segment's timing.
+pub async fn transcribe_subtitles_to_srt_file(
+    ui: &Ui,
+    video: &Video,
+    example_text: &str,
+    lang: Option<&Lang>,
+) -> Result<SubtitleFile> {
+    let json = transcribe_subtitles_to_whisper_json(ui, video, example_text, lang).await?;
+    whisper_json_to_subtitle_file(&json)
+}
+
+/// The result of detecting a video's spoken language.
+#[derive(Debug, Clone)]
+pub struct DetectedLanguage {
+    /// The detected language, or `None` if Whisper identified a language
+    /// we don't have an ISO 639-1 code for (e.g. Cantonese).
+    pub lang: Option<Lang>,
+    /// A rough confidence score in `[0, 1]`, derived from Whisper's
+    /// per-segment average log-probabilities.
+    pub confidence: f64,
+}
+
+/// Detect the language spoken in `video`'s audio track by sampling a
+/// short window of it and running Whisper's language identification.
+pub async fn detect_language(ui: &Ui, video: &Video) -> Result<DetectedLanguage> {
+    ui.status(
+        "detect",
+        &format!(
+            "sampling {:.0}s of audio from {} to detect its language",
+            LANGUAGE_DETECTION_SAMPLE_SECS,
+            video.path().display()
+        ),
+    );
+    let audio_path = extract_audio_file(video.path(), Some(LANGUAGE_DETECTION_SAMPLE_SECS))?;
+    let audio = fs::read(&audio_path);
+    let _ = fs::remove_file(&audio_path);
+    let audio = audio?;
+
+    let api_key = env::var("OPENAI_API_KEY")
+        .map_err(|_| anyhow::anyhow!("OPENAI_API_KEY must be set to use language detection"))?;
+    let form = reqwest::multipart::Form::new()
+        .text("model", "whisper-1")
+        .text("response_format", "verbose_json")
+        .part(
+            "file",
+            reqwest::multipart::Part::bytes(audio)
+                .file_name("sample.wav")
+                .mime_str("audio/wav")?,
+        );
+    let response = reqwest::Client::new()
+        .post("https://api.openai.com/v1/audio/transcriptions")
+        .bearer_auth(api_key)
+        .multipart(form)
+        .send()
+        .await?
+        .error_for_status()?;
+    let json: serde_json::Value = response.json().await?;
+
+    let name = json
+        .get("language")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Whisper response had no \"language\" field"))?;
+    let lang = whisper_language_to_lang(name)?;
+    let confidence = estimate_confidence(&json);
+    match &lang {
+        Some(lang) => ui.status(
+            "detect",
+            &format!("detected {} with confidence {:.2}", lang, confidence),
+        ),
+        None => ui.status(
+            "detect",
+            &format!(
+                "Whisper detected {:?}, which has no ISO 639-1 code; leaving it unset",
+                name
+            ),
+        ),
+    }
+    Ok(DetectedLanguage { lang, confidence })
+}
+
+/// Look up the ISO 639-1 code for a language name as reported by
+/// Whisper's `language` field (e.g. `"english"`). Returns `Ok(None)`
+/// rather than an error if Whisper reports a language we don't have a
+/// code for, so one unmapped stream doesn't abort a whole command.
+fn whisper_language_to_lang(name: &str) -> Result<Option<Lang>> {
+    let code = WHISPER_LANGUAGE_CODES
+        .iter()
+        .find(|(whisper_name, _)| whisper_name.eq_ignore_ascii_case(name))
+        .map(|(_, code)| *code);
+    code.map(Lang::iso639).transpose()
+}
+
+/// Approximate a `[0, 1]` confidence score from Whisper's per-segment
+/// average log-probabilities, since the transcription API doesn't expose
+/// a language-detection confidence directly.
+fn estimate_confidence(json: &serde_json::Value) -> f64 {
+    let segments = match json.get("segments").and_then(|s| s.as_array()) {
+        Some(segments) if !segments.is_empty() => segments,
+        _ => return 0.0,
+    };
+    let avg_logprob: f64 = segments
+        .iter()
+        .filter_map(|s| s.get("avg_logprob").and_then(|v| v.as_f64()))
+        .sum::<f64>()
+        / segments.len() as f64;
+    avg_logprob.exp().clamp(0.0, 1.0)
+}
+
+fn whisper_json_to_subtitle_file(json: &serde_json::Value) -> Result<SubtitleFile> {
+    let segments = json
+        .get("segments")
+        .and_then(|s| s.as_array())
+        .ok_or_else(|| anyhow::anyhow!("Whisper response had no \"segments\" field"))?;
+    let mut cues = Vec::with_capacity(segments.len());
+    for (i, segment) in segments.iter().enumerate() {
+        let begin = segment
+            .get("start")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0);
+        let end = segment.get("end").and_then(|v| v.as_f64()).unwrap_or(begin);
+        let text = segment
+            .get("text")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .trim()
+            .to_owned();
+        cues.push(Cue::new(i + 1, Period::new(begin, end)?, vec![text]));
+    }
+    Ok(SubtitleFile::new(cues))
+}
+
+/// Extract `video_path`'s audio track to a temporary mono WAV file,
+/// suitable for uploading to Whisper. If `duration_secs` is given, only
+/// that many seconds from the start of the track are extracted.
+fn extract_audio_file(video_path: &Path, duration_secs: Option<f64>) -> Result<PathBuf> {
+    let out_path =
+        env::temp_dir().join(format!("substudy-audio-{}.wav", std::process::id()));
+    let mut command = std::process::Command::new("ffmpeg");
+    command.args(["-y", "-v", "quiet", "-i"]).arg(video_path);
+    if let Some(duration_secs) = duration_secs {
+        command.args(["-t", &duration_secs.to_string()]);
+    }
+    let status = command
+        .args(["-vn", "-ac", "1", "-ar"])
+        .arg(AUDIO_SAMPLE_RATE.to_string())
+        .arg(&out_path)
+        .status()
+        .map_err(|e| anyhow::anyhow!("could not run ffmpeg on {}: {}", video_path.display(), e))?;
+    if !status.success() {
+        return Err(anyhow::anyhow!(
+            "ffmpeg failed to extract audio from {}",
+            video_path.display()
+        ));
+    }
+    Ok(out_path)
+}
+
+/// A persistent, on-disk cache of translations, keyed by a hash of the
+/// source text and the target language, so re-translating a file (or
+/// translating overlapping cues across files) doesn't re-hit the API.
+struct TranslationCache {
+    path: PathBuf,
+    entries: HashMap<String, String>,
+}
+
+impl TranslationCache {
+    fn load(path: PathBuf) -> TranslationCache {
+        let entries = fs::read_to_string(&path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        TranslationCache { path, entries }
+    }
+
+    fn default_path() -> PathBuf {
+        env::temp_dir().join("substudy-translation-cache.json")
+    }
+
+    fn key(text: &str, lang: &Lang) -> String {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        text.hash(&mut hasher);
+        format!("{:016x}-{}", hasher.finish(), lang.as_str())
+    }
+
+    fn get(&self, text: &str, lang: &Lang) -> Option<String> {
+        self.entries.get(&Self::key(text, lang)).cloned()
+    }
+
+    fn insert(&mut self, text: &str, lang: &Lang, translation: String) {
+        self.entries.insert(Self::key(text, lang), translation);
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&self.entries)?)?;
+        Ok(())
+    }
+}
+
+/// Translate `file` into every language in `langs`, reusing a persistent
+/// on-disk cache of past translations and retrying rate-limited or
+/// otherwise transient requests with exponential backoff (honoring any
+/// `Retry-After` header OpenAI sends us). Returns one translated
+/// [`SubtitleFile`] per language, in the same order as `langs`.
+pub async fn translate_subtitle_files(
+    ui: &Ui,
+    file: &SubtitleFile,
+    langs: &[Lang],
+) -> Result<Vec<(Lang, SubtitleFile)>> {
+    let api_key = env::var("OPENAI_API_KEY")
+        .map_err(|_| anyhow::anyhow!("OPENAI_API_KEY must be set to use translation"))?;
+    let client = reqwest::Client::new();
+    let cache = Arc::new(Mutex::new(TranslationCache::load(
+        TranslationCache::default_path(),
+    )));
+    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS));
+
+    let mut results = Vec::with_capacity(langs.len());
+    for lang in langs {
+        let mut tasks = Vec::with_capacity(file.cues.len());
+        for cue in &file.cues {
+            let client = client.clone();
+            let api_key = api_key.clone();
+            let cache = Arc::clone(&cache);
+            let semaphore = Arc::clone(&semaphore);
+            let lang = lang.clone();
+            let text = cue.lines.join("\n");
+            tasks.push(tokio::spawn(async move {
+                if let Some(cached) = cache.lock().expect("cache lock poisoned").get(&text, &lang)
+                {
+                    return Ok(cached);
+                }
+                let _permit = semaphore
+                    .acquire()
+                    .await
+                    .expect("translation semaphore was closed early");
+                let translation = translate_text(&client, &api_key, &text, &lang).await?;
+                cache
+                    .lock()
+                    .expect("cache lock poisoned")
+                    .insert(&text, &lang, translation.clone());
+                Ok::<String, anyhow::Error>(translation)
+            }));
+        }
+
+        let mut cues = Vec::with_capacity(file.cues.len());
+        let mut translated = Ok(());
+        for (cue, task) in file.cues.iter().zip(tasks) {
+            match task.await? {
+                Ok(translation) => {
+                    let lines = translation.lines().map(|l| l.to_owned()).collect();
+                    cues.push(Cue::new(cue.index, cue.period, lines));
+                }
+                Err(e) => {
+                    translated = Err(e);
+                    break;
+                }
+            }
+        }
+
+        // Persist whatever this language (and any earlier ones) managed
+        // to translate before worrying about `translated`, so a later
+        // failure doesn't discard already-paid-for API calls.
+        cache.lock().expect("cache lock poisoned").save()?;
+        translated?;
+
+        ui.status(
+            "translate",
+            &format!("translated {} cues into {}", cues.len(), lang),
+        );
+        results.push((lang.clone(), SubtitleFile::new(cues)));
+    }
+
+    Ok(results)
+}
+
+/// Translate a single piece of text via OpenAI's chat API, retrying with
+/// exponential backoff on rate limits (429) and server errors (5xx).
+async fn translate_text(
+    client: &reqwest::Client,
+    api_key: &str,
+    text: &str,
+    lang: &Lang,
+) -> Result<String> {
+    let mut attempt = 0;
+    loop {
+        let response = client
+            .post("https://api.openai.com/v1/chat/completions")
+            .bearer_auth(api_key)
+            .json(&serde_json::json!({
+                "model": "gpt-4o-mini",
+                "messages": [
+                    {
+                        "role": "system",
+                        "content": format!(
+                            "Translate the subtitle line the user gives you into the \
+                             language with ISO 639-1 code {}. Reply with only the \
+                             translation, preserving line breaks.",
+                            lang,
+                        ),
+                    },
+                    { "role": "user", "content": text },
+                ],
+            }))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            if attempt >= MAX_RETRIES {
+                return Err(anyhow::anyhow!(
+                    "translation request failed after {} retries: {}",
+                    attempt,
+                    status
+                ));
+            }
+            let wait = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(Duration::from_secs)
+                .unwrap_or_else(|| Duration::from_secs(2u64.pow(attempt)));
+            attempt += 1;
+            tokio::time::sleep(wait).await;
+            continue;
+        }
+
+        let body: ChatCompletionResponse = response.error_for_status()?.json().await?;
+        return body
+            .choices
+            .into_iter()
+            .next()
+            .map(|c| c.message.content.trim().to_owned())
+            .ok_or_else(|| anyhow::anyhow!("OpenAI returned no translation choices"));
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatCompletionChoice>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ChatCompletionChoice {
+    message: ChatMessage,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ChatMessage {
+    content: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::Lang;
+
+    #[test]
+    fn cache_key_differs_by_text_and_language() {
+        let en = Lang::iso639("en").unwrap();
+        let de = Lang::iso639("de").unwrap();
+        assert_ne!(
+            TranslationCache::key("hello", &en),
+            TranslationCache::key("hello", &de)
+        );
+        assert_ne!(
+            TranslationCache::key("hello", &en),
+            TranslationCache::key("goodbye", &en)
+        );
+        assert_eq!(
+            TranslationCache::key("hello", &en),
+            TranslationCache::key("hello", &en)
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Parsing and serialization of SubRip (`.srt`) subtitle files.
+
+use std::{fmt, fs, path::Path};
+
+use crate::{clean::clean_cues, formats::Format, time::Period, Result};
+
+/// A single subtitle cue: a time period during which some lines of text
+/// are displayed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    /// The 1-based index of this cue in the file it came from.
+    pub index: usize,
+    /// The period during which this cue is displayed.
+    pub period: Period,
+    /// The lines of dialog displayed during this cue.
+    pub lines: Vec<String>,
+}
+
+impl Cue {
+    /// Create a new cue.
+    pub fn new(index: usize, period: Period, lines: Vec<String>) -> Cue {
+        Cue {
+            index,
+            period,
+            lines,
+        }
+    }
+}
+
+/// A parsed subtitle file, as a sequence of cues in chronological order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubtitleFile {
+    /// The cues making up this file, in order.
+    pub cues: Vec<Cue>,
+}
+
+impl SubtitleFile {
+    /// Create a subtitle file from `cues`.
+    pub fn new(cues: Vec<Cue>) -> SubtitleFile {
+        SubtitleFile { cues }
+    }
+
+    /// Parse a subtitle file from raw SRT text.
+    pub fn parse_str(data: &str) -> Result<SubtitleFile> {
+        let mut cues = vec![];
+        for block in data.split("\r\n\r\n").flat_map(|b| b.split("\n\n")) {
+            let block = block.trim();
+            if block.is_empty() {
+                continue;
+            }
+            let mut lines = block.lines();
+            let index: usize = lines
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("expected cue index"))?
+                .trim()
+                .parse()?;
+            let times = lines
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("expected cue timing"))?;
+            let (begin, end) = parse_timing(times)?;
+            let text = lines.map(|l| l.trim_end().to_owned()).collect();
+            cues.push(Cue::new(index, Period::new(begin, end)?, text));
+        }
+        Ok(SubtitleFile { cues })
+    }
+
+    /// Load and parse a subtitle file from disk.
+    pub fn from_path(path: &Path) -> Result<SubtitleFile> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("could not read {}: {}", path.display(), e))?;
+        SubtitleFile::parse_str(&data)
+    }
+
+    /// Load a subtitle file from disk and clean it, removing cues that
+    /// don't look like dialog (empty cues and sound effects). The file's
+    /// format (SubRip, WebVTT, ASS/SSA, ...) is auto-detected; use
+    /// [`SubtitleFile::cleaned_from_path_as`] to override detection.
+    pub fn cleaned_from_path(path: &Path) -> Result<SubtitleFile> {
+        SubtitleFile::cleaned_from_path_as(path, None)
+    }
+
+    /// Like [`SubtitleFile::cleaned_from_path`], but with an explicit
+    /// format override instead of auto-detection.
+    pub fn cleaned_from_path_as(path: &Path, format: Option<Format>) -> Result<SubtitleFile> {
+        let file = Format::read_path(path, format)?;
+        Ok(SubtitleFile::new(clean_cues(file.cues)))
+    }
+}
+
+impl fmt::Display for SubtitleFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, cue) in self.cues.iter().enumerate() {
+            writeln!(f, "{}", i + 1)?;
+            writeln!(
+                f,
+                "{} --> {}",
+                format_timestamp(cue.period.begin()),
+                format_timestamp(cue.period.end())
+            )?;
+            for line in &cue.lines {
+                writeln!(f, "{}", line)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Format a time in seconds as an SRT timestamp: `hh:mm:ss,mmm`.
+fn format_timestamp(secs: f64) -> String {
+    let total_ms = (secs * 1000.0).round() as i64;
+    let ms = total_ms % 1000;
+    let total_s = total_ms / 1000;
+    let s = total_s % 60;
+    let total_m = total_s / 60;
+    let m = total_m % 60;
+    let h = total_m / 60;
+    format!("{:02}:{:02}:{:02},{:03}", h, m, s, ms)
+}
+
+/// Parse an SRT timing line of the form `hh:mm:ss,mmm --> hh:mm:ss,mmm`.
+fn parse_timing(line: &str) -> Result<(f64, f64)> {
+    let mut parts = line.splitn(2, "-->");
+    let begin = parts
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("malformed timing line: {:?}", line))?;
+    let end = parts
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("malformed timing line: {:?}", line))?;
+    Ok((parse_timestamp(begin.trim())?, parse_timestamp(end.trim())?))
+}
+
+/// Parse an SRT timestamp of the form `hh:mm:ss,mmm` into seconds.
+fn parse_timestamp(ts: &str) -> Result<f64> {
+    let ts = ts.replace(',', ".");
+    let parts: Vec<&str> = ts.split(':').collect();
+    if parts.len() != 3 {
+        return Err(anyhow::anyhow!("malformed timestamp: {:?}", ts));
+    }
+    let hours: f64 = parts[0].parse()?;
+    let minutes: f64 = parts[1].parse()?;
+    let seconds: f64 = parts[2].parse()?;
+    Ok(hours * 3600.0 + minutes * 60.0 + seconds)
+}
@@ -0,0 +1,278 @@
+//! Audio-anchored subtitle synchronization.
+//!
+//! Re-times a subtitle file to match the speech actually present in a
+//! video's audio track, using the same general approach as Bazarr's
+//! audio-based subsync: turn both the audio and the subtitles into binary
+//! "is something happening right now" signals sampled at a fixed step,
+//! then find the lag that maximizes their cross-correlation.
+
+use crate::{decode, srt::SubtitleFile, ui::Ui, vad, video::Video, Result};
+
+/// How often (in seconds) we sample the speech-presence signal. 10ms
+/// gives us 100Hz, which is plenty of resolution for subtitle sync.
+const STEP_SECS: f64 = 0.010;
+
+/// The sample rate, in Hz, we ask `ffmpeg` to decode audio at. This is
+/// far higher than we need for VAD, but keeps the energy windows in
+/// `vad::speech_signal` accurate.
+const AUDIO_SAMPLE_RATE: u32 = 16_000;
+
+/// Candidate framerate ratios to try: 1.0 (no rate change), plus two
+/// ratios that cover the common 23.976/25 "PAL speedup" mismatch seen
+/// when subtitles were timed against a different video release.
+const FRAMERATE_RATIOS: &[f64] = &[1.0, 23.976 / 25.0, 25.0 / 23.976];
+
+/// Re-time `subs` so that its cues line up with the speech detected in
+/// `video`'s audio track, and report the detected offset via `ui`.
+pub fn sync_subtitle_file(ui: &Ui, video: &Video, subs: &SubtitleFile) -> Result<SubtitleFile> {
+    let samples = decode::decode_mono_samples(video.path(), AUDIO_SAMPLE_RATE)?;
+    let audio_signal = vad::speech_signal(&samples, AUDIO_SAMPLE_RATE, STEP_SECS);
+    let audio_signal: Vec<f64> = audio_signal
+        .into_iter()
+        .map(|present| if present { 1.0 } else { 0.0 })
+        .collect();
+
+    let mut best: Option<(f64, f64, f64)> = None; // (ratio, offset, score)
+    for &ratio in FRAMERATE_RATIOS {
+        let sub_signal = rasterize(subs, ratio, STEP_SECS, audio_signal.len());
+        let (lag, score) = best_lag(&audio_signal, &sub_signal);
+        // `best_lag(audio, subs)` returns how far the subtitle signal
+        // trails the audio signal, so cues need to move *earlier* by
+        // that amount to line up with the speech.
+        let offset = -(lag as f64) * STEP_SECS;
+        if best
+            .map(|(_, _, best_score)| score > best_score)
+            .unwrap_or(true)
+        {
+            best = Some((ratio, offset, score));
+        }
+    }
+    let (ratio, offset, _score) = best.expect("FRAMERATE_RATIOS is non-empty");
+
+    ui.status(
+        "sync",
+        &format!(
+            "detected offset {:+.3}s at rate ratio {:.6} (shift = ratio * t + offset)",
+            offset, ratio
+        ),
+    );
+
+    let cues = subs
+        .cues
+        .iter()
+        .map(|cue| {
+            let mut cue = cue.clone();
+            cue.period = cue.period.transformed(ratio, offset);
+            cue
+        })
+        .collect();
+    Ok(SubtitleFile::new(cues))
+}
+
+/// Rasterize `subs` into a binary signal sampled every `step_secs`
+/// seconds, after first applying the framerate `ratio` to its
+/// timestamps. The signal is zero-padded or truncated to exactly `len`
+/// samples so it lines up with the audio signal for correlation.
+fn rasterize(subs: &SubtitleFile, ratio: f64, step_secs: f64, len: usize) -> Vec<f64> {
+    let mut signal = vec![0.0; len];
+    for cue in &subs.cues {
+        let period = cue.period.transformed(ratio, 0.0);
+        let begin = (period.begin() / step_secs).floor() as usize;
+        let end = ((period.end() / step_secs).ceil() as usize).min(len);
+        for sample in signal.iter_mut().take(end).skip(begin.min(len)) {
+            *sample = 1.0;
+        }
+    }
+    signal
+}
+
+/// Find the lag (in samples of `a`) that maximizes the cross-correlation
+/// of `a` and `b`, where a positive lag means `b` trails `a` (so `b`
+/// should be shifted earlier in time to line up with `a`). Returns
+/// `(lag, correlation_at_lag)`.
+///
+/// Computed via FFT: `corr = IFFT(conj(FFT(a)) * FFT(b))`.
+fn best_lag(a: &[f64], b: &[f64]) -> (isize, f64) {
+    let n = a.len().max(b.len());
+    let fft_len = (2 * n).next_power_of_two();
+
+    let mut fa = pad_to_complex(a, fft_len);
+    let mut fb = pad_to_complex(b, fft_len);
+    fft(&mut fa, false);
+    fft(&mut fb, false);
+
+    let mut product: Vec<Complex> = fa
+        .iter()
+        .zip(fb.iter())
+        .map(|(x, y)| x.conj().mul(y))
+        .collect();
+    fft(&mut product, true);
+
+    // `product[k]` for k in [0, n] is the correlation at lag +k (b
+    // trailing a); `product[fft_len - k]` is the correlation at lag -k
+    // (b leading a). We search both halves for the best match.
+    let mut best_lag = 0_isize;
+    let mut best_score = f64::MIN;
+    let max_lag = n as isize;
+    for lag in -max_lag..=max_lag {
+        let index = lag.rem_euclid(fft_len as isize) as usize;
+        let score = product[index].re;
+        if score > best_score {
+            best_score = score;
+            best_lag = lag;
+        }
+    }
+    (best_lag, best_score)
+}
+
+fn pad_to_complex(signal: &[f64], len: usize) -> Vec<Complex> {
+    let mut out = vec![Complex::ZERO; len];
+    for (i, &v) in signal.iter().enumerate() {
+        out[i] = Complex::new(v, 0.0);
+    }
+    out
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    const ZERO: Complex = Complex { re: 0.0, im: 0.0 };
+
+    fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    fn mul(self, other: &Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+/// An iterative, in-place radix-2 Cooley-Tukey FFT. `data.len()` must be a
+/// power of two. Pass `inverse = true` to compute an inverse transform
+/// (the result is scaled by `1 / data.len()`, matching a normalized
+/// IFFT).
+fn fft(data: &mut [Complex], inverse: bool) {
+    let n = data.len();
+    if n <= 1 {
+        return;
+    }
+    debug_assert!(n.is_power_of_two());
+
+    // Bit-reversal permutation.
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j &= !bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * std::f64::consts::PI / len as f64;
+        let wlen = Complex::new(angle.cos(), angle.sin());
+        let mut start = 0;
+        while start < n {
+            let mut w = Complex::new(1.0, 0.0);
+            for k in 0..len / 2 {
+                let u = data[start + k];
+                let v = data[start + k + len / 2].mul(&w);
+                data[start + k] = u.add(v);
+                data[start + k + len / 2] = u.sub(v);
+                w = w.mul(&wlen);
+            }
+            start += len;
+        }
+        len <<= 1;
+    }
+
+    if inverse {
+        for c in data.iter_mut() {
+            c.re /= n as f64;
+            c.im /= n as f64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fft_round_trips_through_its_inverse() {
+        let original = [1.0, 2.0, 3.0, 4.0];
+        let mut data = pad_to_complex(&original, 4);
+        fft(&mut data, false);
+        fft(&mut data, true);
+        for (c, &expected) in data.iter().zip(original.iter()) {
+            assert!((c.re - expected).abs() < 1e-9);
+            assert!(c.im.abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn best_lag_finds_a_known_shift() {
+        // `b` is `a` shifted 3 samples later, so `b[i] == a[i - 3]`.
+        let a = [0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0];
+        let b = [0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0];
+        let (lag, _score) = best_lag(&a, &b);
+        assert_eq!(lag, 3);
+    }
+
+    #[test]
+    fn offset_computed_from_lag_moves_a_late_cue_toward_the_true_timing() {
+        use crate::{srt::Cue, time::Period};
+
+        // Speech actually happens at [0.20, 0.30]s; the subtitle claims
+        // it happens at [0.50, 0.60]s, i.e. it's 300ms (30 samples) late.
+        let mut audio_signal = vec![0.0; 100];
+        audio_signal[20..30].fill(1.0);
+        let subs = SubtitleFile::new(vec![Cue::new(
+            1,
+            Period::new(0.50, 0.60).unwrap(),
+            vec!["late".to_owned()],
+        )]);
+
+        let sub_signal = rasterize(&subs, 1.0, STEP_SECS, audio_signal.len());
+        let (lag, _score) = best_lag(&audio_signal, &sub_signal);
+        let offset = -(lag as f64) * STEP_SECS;
+
+        let synced = subs.cues[0].period.transformed(1.0, offset);
+        // The synced cue should land close to the true speech timing,
+        // not be pushed even further away from it.
+        assert!(
+            (synced.begin() - 0.20).abs() < 0.02,
+            "expected synced begin near 0.20s, got {}",
+            synced.begin()
+        );
+        assert!(
+            (synced.begin() - 0.20).abs() < (0.50 - 0.20).abs(),
+            "sync moved the cue away from the true timing instead of toward it"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Time periods used to represent subtitle cue timing.
+
+use std::fmt;
+
+use crate::Result;
+
+/// A span of time, in seconds, with an inclusive beginning and an
+/// exclusive end.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Period {
+    begin: f64,
+    end: f64,
+}
+
+impl Period {
+    /// Create a new period running from `begin` to `end`, both in seconds.
+    pub fn new(begin: f64, end: f64) -> Result<Period> {
+        if begin > end {
+            return Err(anyhow::anyhow!(
+                "period begin {} is after end {}",
+                begin,
+                end
+            ));
+        }
+        Ok(Period { begin, end })
+    }
+
+    /// The time at which this period begins, in seconds.
+    pub fn begin(&self) -> f64 {
+        self.begin
+    }
+
+    /// The time at which this period ends, in seconds.
+    pub fn end(&self) -> f64 {
+        self.end
+    }
+
+    /// Shift this period by `offset` seconds, clamping so it never starts
+    /// before zero.
+    pub fn shifted(&self, offset: f64) -> Period {
+        self.transformed(1.0, offset)
+    }
+
+    /// Apply the affine transform `t' = ratio * t + offset` to both ends
+    /// of this period, clamping the result so it never starts before
+    /// zero.
+    pub fn transformed(&self, ratio: f64, offset: f64) -> Period {
+        let begin = (self.begin * ratio + offset).max(0.0);
+        let end = (self.end * ratio + offset).max(begin);
+        Period { begin, end }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.3}-{:.3}", self.begin, self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transformed_applies_the_affine_shift() {
+        let period = Period::new(10.0, 12.0).unwrap();
+        let transformed = period.transformed(2.0, 1.0);
+        assert_eq!(transformed.begin(), 21.0);
+        assert_eq!(transformed.end(), 25.0);
+    }
+
+    #[test]
+    fn transformed_clamps_to_zero() {
+        let period = Period::new(1.0, 2.0).unwrap();
+        let transformed = period.transformed(1.0, -10.0);
+        assert_eq!(transformed.begin(), 0.0);
+        assert_eq!(transformed.end(), 0.0);
+    }
+}
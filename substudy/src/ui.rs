@@ -0,0 +1,21 @@
+//! A small abstraction over user-facing status output, so that the rest
+//! of the tool doesn't need to care whether it's talking to a terminal or
+//! (eventually) some other front end.
+
+/// Reports progress and informational messages to the user.
+#[derive(Debug, Clone)]
+pub struct Ui {
+    _private: (),
+}
+
+impl Ui {
+    /// Set up a new `Ui`.
+    pub fn init() -> Ui {
+        Ui { _private: () }
+    }
+
+    /// Print an informational status message.
+    pub fn status(&self, category: &str, message: &str) {
+        eprintln!("[{}] {}", category, message);
+    }
+}
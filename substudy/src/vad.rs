@@ -0,0 +1,34 @@
+//! A simple energy-based voice activity detector (VAD).
+//!
+//! This isn't meant to be a state-of-the-art VAD, just a cheap way to turn
+//! a PCM signal into a "speech present" signal that other parts of the
+//! tool (subtitle synchronization) can correlate against.
+
+/// Compute a binary "speech present" signal from `samples`, sampled at
+/// `sample_rate` Hz, down-sampled to one value every `step_secs` seconds.
+///
+/// Each output value is `true` if the RMS energy of the corresponding
+/// window exceeds a fixed threshold relative to the track's peak energy.
+pub fn speech_signal(samples: &[f32], sample_rate: u32, step_secs: f64) -> Vec<bool> {
+    const RELATIVE_THRESHOLD: f32 = 0.05;
+
+    let window_len = ((sample_rate as f64) * step_secs).round().max(1.0) as usize;
+    if samples.is_empty() || window_len == 0 {
+        return vec![];
+    }
+
+    let energies: Vec<f32> = samples
+        .chunks(window_len)
+        .map(|window| {
+            let sum_sq: f32 = window.iter().map(|s| s * s).sum();
+            (sum_sq / window.len() as f32).sqrt()
+        })
+        .collect();
+
+    let peak = energies.iter().cloned().fold(0.0_f32, f32::max);
+    if peak <= 0.0 {
+        return vec![false; energies.len()];
+    }
+    let threshold = peak * RELATIVE_THRESHOLD;
+    energies.into_iter().map(|e| e > threshold).collect()
+}
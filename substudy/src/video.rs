@@ -0,0 +1,170 @@
+//! Reading metadata out of video files using `ffprobe`.
+
+use std::{path::Path, path::PathBuf, process::Command};
+
+use serde::Deserialize;
+
+use crate::{lang::Lang, Result};
+
+/// The kind of data carried by a stream inside a video container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodecType {
+    /// A video stream.
+    Video,
+    /// An audio stream.
+    Audio,
+    /// A subtitle stream.
+    Subtitle,
+    /// Some other kind of stream we don't care about.
+    Other,
+}
+
+impl CodecType {
+    fn from_ffprobe(s: &str) -> CodecType {
+        match s {
+            "video" => CodecType::Video,
+            "audio" => CodecType::Audio,
+            "subtitle" => CodecType::Subtitle,
+            _ => CodecType::Other,
+        }
+    }
+}
+
+/// A single stream inside a video container.
+#[derive(Debug, Clone)]
+pub struct Stream {
+    /// The stream's index within the container.
+    pub index: usize,
+    /// The kind of data this stream carries.
+    pub codec_type: CodecType,
+    /// The ffmpeg codec name for this stream (e.g. `subrip`, `ass`).
+    pub codec_name: String,
+    language: Option<Lang>,
+}
+
+impl Stream {
+    /// The language tag associated with this stream, if any.
+    pub fn language(&self) -> Option<Lang> {
+        self.language.clone()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct FfprobeOutput {
+    streams: Vec<FfprobeStream>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FfprobeStream {
+    index: usize,
+    codec_type: String,
+    #[serde(default)]
+    codec_name: String,
+    #[serde(default)]
+    tags: FfprobeTags,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct FfprobeTags {
+    language: Option<String>,
+}
+
+/// A video (or audio-only) file on disk.
+#[derive(Debug, Clone)]
+pub struct Video {
+    path: PathBuf,
+    streams: Vec<StreamInfo>,
+}
+
+#[derive(Debug, Clone)]
+struct StreamInfo {
+    index: usize,
+    codec_type: CodecType,
+    codec_name: String,
+    language: Option<Lang>,
+}
+
+impl Video {
+    /// Probe `path` with `ffprobe` to find its streams.
+    pub fn new(path: &Path) -> Result<Video> {
+        let output = Command::new("ffprobe")
+            .args(["-v", "quiet", "-print_format", "json", "-show_streams"])
+            .arg(path)
+            .output()
+            .map_err(|e| anyhow::anyhow!("could not run ffprobe on {}: {}", path.display(), e))?;
+        if !output.status.success() {
+            return Err(anyhow::anyhow!(
+                "ffprobe failed on {}: {}",
+                path.display(),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        let parsed: FfprobeOutput = serde_json::from_slice(&output.stdout)?;
+        let streams = parsed
+            .streams
+            .into_iter()
+            .map(|s| StreamInfo {
+                index: s.index,
+                codec_type: CodecType::from_ffprobe(&s.codec_type),
+                codec_name: s.codec_name,
+                language: s.tags.language.and_then(|l| Lang::iso639(&l).ok()),
+            })
+            .collect();
+        Ok(Video {
+            path: path.to_owned(),
+            streams,
+        })
+    }
+
+    /// The path to this video on disk.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The streams found inside this video's container.
+    pub fn streams(&self) -> impl Iterator<Item = Stream> + '_ {
+        self.streams.iter().map(|s| Stream {
+            index: s.index,
+            codec_type: s.codec_type,
+            codec_name: s.codec_name.clone(),
+            language: s.language.clone(),
+        })
+    }
+
+    /// Find a single subtitle stream by container index or language code.
+    /// If neither is given, succeeds only when the video has exactly one
+    /// subtitle stream.
+    pub fn subtitle_stream(&self, index: Option<usize>, lang: Option<&Lang>) -> Result<Stream> {
+        let subtitle_streams: Vec<Stream> = self
+            .streams()
+            .filter(|s| s.codec_type == CodecType::Subtitle)
+            .collect();
+        if let Some(index) = index {
+            return subtitle_streams
+                .into_iter()
+                .find(|s| s.index == index)
+                .ok_or_else(|| anyhow::anyhow!("no subtitle stream at index {}", index));
+        }
+        if let Some(lang) = lang {
+            return subtitle_streams
+                .into_iter()
+                .find(|s| s.language().as_ref() == Some(lang))
+                .ok_or_else(|| anyhow::anyhow!("no subtitle stream tagged as {}", lang));
+        }
+        match subtitle_streams.len() {
+            0 => Err(anyhow::anyhow!(
+                "{} has no subtitle streams",
+                self.path.display()
+            )),
+            1 => Ok(subtitle_streams
+                .into_iter()
+                .next()
+                .expect("checked len == 1")),
+            n => Err(anyhow::anyhow!(
+                "{} has {} subtitle streams; pick one with --track or --lang",
+                self.path.display(),
+                n
+            )),
+        }
+    }
+}